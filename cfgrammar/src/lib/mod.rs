@@ -0,0 +1,6 @@
+//! Low-level grammar/lexer infrastructure shared between `lrlex` and `lrpar`, so that neither
+//! has to maintain its own copy.
+
+pub mod span;
+
+pub use span::{render, NewlineCache, Span};
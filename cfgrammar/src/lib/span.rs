@@ -0,0 +1,104 @@
+//! Mapping byte offsets in grammar/lexer source text back to human-readable `line:col`
+//! positions, so that build-time errors can point at the offending source rather than being
+//! reported as a bare, location-less message.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A byte range `[start, end)` into a source string.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Span {
+    start: usize,
+    end: usize
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        debug_assert!(start <= end);
+        Span { start, end }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+/// Caches the byte offset of every line start in a source string, so that any byte offset (or
+/// 1-based line number) can be mapped to a human-readable position by binary search, without
+/// rescanning the string. Built once per source string.
+#[derive(Debug)]
+pub struct NewlineCache {
+    /// The byte offset of the first character of each line; `line_starts[0]` is always `0`.
+    line_starts: Vec<usize>
+}
+
+impl NewlineCache {
+    pub fn new(s: &str) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in s.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        NewlineCache { line_starts }
+    }
+
+    fn line_idx(&self, off: usize) -> usize {
+        match self.line_starts.binary_search(&off) {
+            Ok(i) => i,
+            Err(i) => i - 1
+        }
+    }
+
+    /// Map a byte offset to a 1-based `(line, column)` pair.
+    pub fn line_col(&self, off: usize) -> (usize, usize) {
+        let line_idx = self.line_idx(off);
+        (line_idx + 1, off - self.line_starts[line_idx] + 1)
+    }
+
+    /// The `Span` covering the whole of 1-based line `line_no` (excluding its trailing newline).
+    pub fn line_span(&self, s: &str, line_no: usize) -> Span {
+        let idx = line_no.saturating_sub(1).min(self.line_starts.len() - 1);
+        let start = self.line_starts[idx];
+        let end = self
+            .line_starts
+            .get(idx + 1)
+            .map(|&e| e - 1)
+            .unwrap_or_else(|| s.len());
+        Span::new(start, end.max(start))
+    }
+
+    /// The full text of the line containing byte offset `off` in the original source `s`.
+    pub fn line_text<'a>(&self, s: &'a str, off: usize) -> &'a str {
+        let line_idx = self.line_idx(off);
+        let start = self.line_starts[line_idx];
+        let end = self
+            .line_starts
+            .get(line_idx + 1)
+            .map(|&e| e - 1)
+            .unwrap_or_else(|| s.len());
+        &s[start..end.max(start)]
+    }
+}
+
+/// Render `msg` as a `line:col: msg` diagnostic, followed by the offending source line and a
+/// `^` caret underlining `span`.
+pub fn render(s: &str, cache: &NewlineCache, span: Span, msg: &dyn fmt::Display) -> String {
+    let (line, col) = cache.line_col(span.start());
+    let line_text = cache.line_text(s, span.start());
+    let underline_len = (span.end().max(span.start() + 1) - span.start()).max(1);
+    format!(
+        "{}:{}: {}\n    {}\n    {}{}",
+        line,
+        col,
+        msg,
+        line_text,
+        " ".repeat(col.saturating_sub(1)),
+        "^".repeat(underline_len)
+    )
+}
@@ -0,0 +1,42 @@
+extern crate cfgrammar;
+
+use cfgrammar::{render, NewlineCache, Span};
+
+#[test]
+fn test_line_col() {
+    let src = "abc\ndef\nghi";
+    let cache = NewlineCache::new(src);
+    assert_eq!(cache.line_col(0), (1, 1));
+    assert_eq!(cache.line_col(2), (1, 3));
+    assert_eq!(cache.line_col(4), (2, 1));
+    assert_eq!(cache.line_col(9), (3, 2));
+}
+
+#[test]
+fn test_line_text() {
+    let src = "abc\ndef\nghi";
+    let cache = NewlineCache::new(src);
+    assert_eq!(cache.line_text(src, 0), "abc");
+    assert_eq!(cache.line_text(src, 5), "def");
+    assert_eq!(cache.line_text(src, 10), "ghi");
+}
+
+#[test]
+fn test_line_span() {
+    let src = "abc\ndef\nghi";
+    let cache = NewlineCache::new(src);
+    let span = cache.line_span(src, 2);
+    assert_eq!(span, Span::new(4, 7));
+}
+
+#[test]
+fn test_render() {
+    let src = "let x = ;";
+    let cache = NewlineCache::new(src);
+    let span = Span::new(8, 9);
+    let out = render(src, &cache, span, &"Missing expression");
+    assert_eq!(
+        out,
+        "1:9: Missing expression\n    let x = ;\n            ^"
+    );
+}
@@ -13,15 +13,45 @@ use std::{
     path::{Path, PathBuf}
 };
 
+use filetime::FileTime;
 use lazy_static::lazy_static;
 use num_traits::{PrimInt, Unsigned};
 use regex::Regex;
+use serde::Serialize;
 use try_from::TryFrom;
 
 use crate::lexer::{LRNonStreamingLexerDef, LexerDef};
 
 const RUST_FILE_EXT: &str = "rs";
 
+/// A stable hash of `rule_ids_map`'s contents (order-independent), used to detect a changed
+/// rule-ID map even when the `.l` file supplying `rule_ids_sig_line`'s caller hasn't itself
+/// changed.
+fn rule_ids_signature<StorageT: Hash>(rule_ids_map: &Option<HashMap<String, StorageT>>) -> u64 {
+    use std::{
+        collections::hash_map::DefaultHasher,
+        hash::Hasher
+    };
+
+    let mut pairs = match rule_ids_map {
+        Some(m) => m.iter().collect::<Vec<_>>(),
+        None => Vec::new()
+    };
+    pairs.sort_by(|a, b| a.0.cmp(b.0));
+    let mut hasher = DefaultHasher::new();
+    for (name, tok_id) in pairs {
+        name.hash(&mut hasher);
+        tok_id.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// The comment line `rule_ids_signature` is embedded as, so that it can both be written into
+/// generated output and compared against when deciding whether the mtime short-circuit applies.
+fn rule_ids_sig_line(sig: u64) -> String {
+    format!("// rule_ids_signature: {}", sig)
+}
+
 lazy_static! {
     static ref RE_TOKEN_ID: Regex = Regex::new(r"^[a-zA-Z_][a-zA-Z_0-9]*$").unwrap();
 }
@@ -30,11 +60,53 @@ pub enum LexerKind {
     LRNonStreamingLexer
 }
 
+/// The visibility to give the module (and the items within it) generated by
+/// [`LexerBuilder`](struct.LexerBuilder.html). Defaults to `Public`.
+#[derive(Clone, Debug)]
+pub enum Visibility {
+    /// Equivalent to no visibility modifier at all (i.e. private to its parent module).
+    Private,
+    /// `pub`.
+    Public,
+    /// `pub(crate)`.
+    PublicCrate,
+    /// `pub(in path::to::some::module)`.
+    PublicIn(String)
+}
+
+impl Visibility {
+    fn prefix(&self) -> String {
+        match self {
+            Visibility::Private => String::new(),
+            Visibility::Public => "pub ".to_owned(),
+            Visibility::PublicCrate => "pub(crate) ".to_owned(),
+            Visibility::PublicIn(ref p) => format!("pub(in {}) ", p)
+        }
+    }
+}
+
+/// The Rust edition that generated code should target. Defaults to `Rust2018`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RustEdition {
+    Rust2015,
+    Rust2018,
+    Rust2021
+}
+
+impl RustEdition {
+    /// Pre-2018 editions require an explicit `extern crate` for every crate referenced by path.
+    fn needs_extern_crate(self) -> bool {
+        self == RustEdition::Rust2015
+    }
+}
+
 /// A `LexerBuilder` allows one to specify the criteria for building a statically generated
 /// lexer.
 pub struct LexerBuilder<'a, StorageT = u32> {
     lexerkind: LexerKind,
     mod_name: Option<&'a str>,
+    visibility: Visibility,
+    rust_edition: RustEdition,
     rule_ids_map: Option<HashMap<String, StorageT>>,
     allow_missing_terms_in_lexer: bool,
     allow_missing_tokens_in_parser: bool
@@ -42,7 +114,7 @@ pub struct LexerBuilder<'a, StorageT = u32> {
 
 impl<'a, StorageT> LexerBuilder<'a, StorageT>
 where
-    StorageT: Copy + Debug + Eq + Hash + PrimInt + TryFrom<usize> + Unsigned
+    StorageT: Copy + Debug + Eq + Hash + PrimInt + Serialize + TryFrom<usize> + Unsigned
 {
     /// Create a new `LexerBuilder`.
     ///
@@ -64,12 +136,28 @@ where
         LexerBuilder {
             lexerkind: LexerKind::LRNonStreamingLexer,
             mod_name: None,
+            visibility: Visibility::Public,
+            rust_edition: RustEdition::Rust2018,
             rule_ids_map: None,
             allow_missing_terms_in_lexer: false,
             allow_missing_tokens_in_parser: true
         }
     }
 
+    /// Set the visibility of the generated module (and the items within it, i.e. `lexerdef()` and
+    /// the `T_*` token constants) to `visibility`. Defaults to `Visibility::Public`.
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Set the Rust edition that the generated code should target. Defaults to
+    /// `RustEdition::Rust2018`.
+    pub fn rust_edition(mut self, rust_edition: RustEdition) -> Self {
+        self.rust_edition = rust_edition;
+        self
+    }
+
     /// Set the type of lexer to be generated to `lexerkind`.
     pub fn lexerkind(mut self, lexerkind: LexerKind) -> Self {
         self.lexerkind = lexerkind;
@@ -138,6 +226,22 @@ where
     ///    * or, if no module name was explicitly specified, then for the file `/a/b/c.l` the
     ///      module name is `c_l` (i.e. the file's leaf name, minus its extension, with a prefix of
     ///      `_l`).
+    ///
+    /// If `outp` already exists, is newer than `inp`, and was generated from the same
+    /// [`rule_ids_map`](#method.rule_ids_map), this is a no-op: only the first line of `outp` is
+    /// read, and `inp` is neither read nor parsed and `outp` is not rewritten, since an unchanged input and an unchanged rule-ID map can only produce
+    /// unchanged output. `rule_ids_map` is included in that check (rather than just `inp`'s
+    /// mtime) because it can change independently of the `.l` file -- typically because a
+    /// paired `.y` grammar gained or lost a token -- and doing so must still re-run the
+    /// missing-token validation below rather than silently reusing a blob built against the old
+    /// map. The generated `lexerdef()` itself doesn't reconstruct `Rule`s from source: the
+    /// validated tables are serialized once, here, into a binary blob embedded in the output,
+    /// which `lexerdef()` deserializes each time it is called.
+    ///
+    /// Parse errors in `inp`, and tokens defined in the lexer but missing from the grammar, are
+    /// reported as `line:col` diagnostics with the offending source line and a caret underline,
+    /// rather than a bare message. Tokens used in the grammar but missing from the lexer have no
+    /// line in `inp`, and are listed by name only.
     pub fn process_file<P, Q>(
         self,
         inp: P,
@@ -147,9 +251,29 @@ where
         P: AsRef<Path>,
         Q: AsRef<Path>
     {
-        let mut lexerdef: Box<dyn LexerDef<StorageT>> = match self.lexerkind {
+        // If the input hasn't changed since we last generated `outp`, there's no need to even
+        // read and reparse it: building the tables is the expensive part of this function, and
+        // an unchanged `.l` file (validated against an unchanged `rule_ids_map`) can only
+        // produce unchanged tables. The rule-ID map's signature is embedded as the first line of
+        // `outp`, so a `.y` grammar that adds/removes a token still forces re-validation even
+        // though `inp` itself didn't change.
+        let rule_ids_sig = rule_ids_signature(&self.rule_ids_map);
+        if let (Ok(in_meta), Ok(out_meta)) = (fs::metadata(&inp), fs::metadata(&outp)) {
+            let in_mtime = FileTime::from_last_modification_time(&in_meta);
+            let out_mtime = FileTime::from_last_modification_time(&out_meta);
+            if out_mtime >= in_mtime {
+                if let Ok(existing) = read_to_string(&outp) {
+                    if existing.lines().next() == Some(rule_ids_sig_line(rule_ids_sig).as_str()) {
+                        return Ok((None, None));
+                    }
+                }
+            }
+        }
+
+        let src = read_to_string(&inp)?;
+        let mut lexerdef = match self.lexerkind {
             LexerKind::LRNonStreamingLexer => {
-                Box::new(LRNonStreamingLexerDef::from_str(&read_to_string(&inp)?)?)
+                LRNonStreamingLexerDef::from_str(&src).map_err(|e| e.pp(&src))?
             }
         };
         let (missing_from_lexer, missing_from_parser) = match self.rule_ids_map {
@@ -169,24 +293,37 @@ where
             None => (None, None)
         };
 
+        // Every rule that actually came from parsing `src` carries the span of the line it was
+        // declared on, which lets us point the "missing from grammar" diagnostic at the
+        // offending `.l` line rather than just naming it.
+        let cache = cfgrammar::NewlineCache::new(&src);
         if !self.allow_missing_terms_in_lexer {
             if let Some(ref mfl) = missing_from_lexer {
-                eprintln!("Error: the following tokens are used in the grammar but are not defined in the lexer:");
+                let mut msg = "the following tokens are used in the grammar but are not defined in the lexer:\n".to_owned();
                 for n in mfl {
-                    eprintln!("    {}", n);
+                    msg.push_str(&format!("    {}\n", n));
                 }
                 fs::remove_file(&outp).ok();
-                panic!();
+                panic!("{}", msg);
             }
         }
         if !self.allow_missing_tokens_in_parser {
             if let Some(ref mfp) = missing_from_parser {
-                eprintln!("Error: the following tokens are defined in the lexer but not used in the grammar:");
+                let mut msg =
+                    "the following tokens are defined in the lexer but not used in the grammar:\n"
+                        .to_owned();
                 for n in mfp {
-                    eprintln!("    {}", n);
+                    match lexerdef.iter_rules().find(|r| r.name.as_deref() == Some(n.as_str())) {
+                        Some(r) if r.name_span.is_some() => {
+                            let span = r.name_span.unwrap();
+                            msg.push_str(&cfgrammar::render(&src, &cache, span, n));
+                            msg.push('\n');
+                        }
+                        _ => msg.push_str(&format!("    {}\n", n))
+                    }
                 }
                 fs::remove_file(&outp).ok();
-                panic!();
+                panic!("{}", msg);
             }
         }
 
@@ -210,54 +347,47 @@ where
         };
 
         let mut outs = String::new();
+        outs.push_str(&rule_ids_sig_line(rule_ids_sig));
+        outs.push('\n');
         //
         // Header
 
-        let (lexerdef_name, lexerdef_type) = match self.lexerkind {
-            LexerKind::LRNonStreamingLexer => (
-                "LRNonStreamingLexerDef",
+        let lexerdef_type = match self.lexerkind {
+            LexerKind::LRNonStreamingLexer => {
                 format!("LRNonStreamingLexerDef<{}>", type_name::<StorageT>())
-            )
+            }
         };
 
-        outs.push_str(&format!(
-            "mod {mod_name} {{
-use lrlex::{{LexerDef, LRNonStreamingLexerDef, Rule}};
-
-#[allow(dead_code)]
-pub fn lexerdef() -> {lexerdef_type} {{
-    let rules = vec![",
-            mod_name = mod_name,
-            lexerdef_type = lexerdef_type
-        ));
+        // Building the tables is the expensive part of this function; constructing every `Rule`
+        // from source literals every time a dependent crate is compiled is not. So instead of
+        // emitting constructor calls, we serialize the already-validated `LexerDef` once, here,
+        // and have the generated code simply deserialize it.
+        let bin = bincode::serialize(&lexerdef)?;
 
-        // Individual rules
-        for r in lexerdef.iter_rules() {
-            let tok_id = match r.tok_id {
-                Some(ref t) => format!("Some({:?})", t),
-                None => "None".to_owned()
-            };
-            let n = match r.name {
-                Some(ref n) => format!("Some({:?}.to_string())", n),
-                None => "None".to_owned()
-            };
-            outs.push_str(&format!(
-                "
-Rule::new({}, {}, \"{}\".to_string()).unwrap(),",
-                tok_id,
-                n,
-                r.re_str.replace("\\", "\\\\").replace("\"", "\\\"")
-            ));
-        }
+        let vis = self.visibility.prefix();
+        let externs = if self.rust_edition.needs_extern_crate() {
+            "extern crate bincode;\nextern crate lrlex;\n"
+        } else {
+            ""
+        };
 
-        // Footer
         outs.push_str(&format!(
-            "
-];
-    {lexerdef_name}::from_rules(rules)
+            "{vis}mod {mod_name} {{
+{externs}use lrlex::{{LexerDef, LRNonStreamingLexerDef}};
+
+static LEXERDEF_BYTES: [u8; {len}] = {bytes:?};
+
+#[allow(dead_code)]
+{vis}fn lexerdef() -> {lexerdef_type} {{
+    bincode::deserialize(&LEXERDEF_BYTES).unwrap()
 }}
 ",
-            lexerdef_name = lexerdef_name
+            vis = vis,
+            externs = externs,
+            mod_name = mod_name,
+            lexerdef_type = lexerdef_type,
+            len = bin.len(),
+            bytes = bin
         ));
 
         // Token IDs
@@ -265,10 +395,11 @@ Rule::new({}, {}, \"{}\".to_string()).unwrap(),",
             for (n, id) in rim {
                 if RE_TOKEN_ID.is_match(n) {
                     outs.push_str(&format!(
-                        "#[allow(dead_code)]\npub const T_{}: {} = {:?};\n",
+                        "#[allow(dead_code)]\n{vis}const T_{}: {} = {:?};\n",
                         n.to_ascii_uppercase(),
                         type_name::<StorageT>(),
-                        *id
+                        *id,
+                        vis = vis
                     ));
                 }
             }
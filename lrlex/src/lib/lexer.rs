@@ -0,0 +1,636 @@
+//! The types used to represent a compiled `.l` file, and the run-time support for matching
+//! against it.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    slice::Iter
+};
+
+use cfgrammar::{render, NewlineCache, Span};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Uniquely identifies a start state (a.k.a. "start condition") declared in a `.l` file.
+///
+/// The state `INITIAL` is always present and always has id `0`: every lexer begins matching in
+/// it, and every rule with no `<...>` prefix implicitly belongs to it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct StartStateId(usize);
+
+impl StartStateId {
+    pub fn new(i: usize) -> Self {
+        StartStateId(i)
+    }
+
+    pub fn as_usize(&self) -> usize {
+        self.0
+    }
+}
+
+/// The id of the state that every lexer starts in.
+pub const INITIAL: StartStateId = StartStateId(0);
+
+/// A start state declared with `%x` (exclusive) or `%s` (inclusive) in a `.l` file's header.
+///
+/// An inclusive state also matches rules which don't have an explicit `<...>` prefix (i.e. the
+/// rules that belong to `INITIAL`); an exclusive state only matches rules explicitly tagged with
+/// its name.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StartState {
+    pub id: StartStateId,
+    pub name: String,
+    pub exclusive: bool
+}
+
+impl StartState {
+    pub fn new(id: StartStateId, name: String, exclusive: bool) -> Self {
+        StartState { id, name, exclusive }
+    }
+}
+
+/// What a successful match against a [`Rule`](struct.Rule.html) does to the lexer's start-state
+/// stack.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum StartStateOperation {
+    /// Replace the whole stack with a single new state (flex's `BEGIN`).
+    ReplaceStack(StartStateId),
+    /// Push a new state on top of the stack.
+    Push(StartStateId),
+    /// Pop the current state off the stack, reverting to whatever was active before.
+    Pop
+}
+
+/// A single rule in a `.l` file: if `re_str` matches, then `tok_id` is returned (if it is
+/// `None`, the matching text is discarded e.g. for whitespace).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Rule<StorageT> {
+    /// If `None`, the rule matches but does not produce a token (e.g. for whitespace).
+    pub tok_id: Option<StorageT>,
+    /// The name of this rule, used to synchronise lexer and parser token IDs.
+    pub name: Option<String>,
+    /// The regular expression this rule matches against.
+    pub re_str: String,
+    /// The start states this rule is active in. An empty vector means the rule has no `<...>`
+    /// prefix: it is active in `INITIAL` and in every inclusive start state.
+    pub start_states: Vec<StartStateId>,
+    /// What to do to the start-state stack if this rule matches.
+    pub start_state_op: Option<StartStateOperation>,
+    /// The span of the `.l` source line this rule was declared on, if it was parsed from source
+    /// (`None` if it was constructed directly with [`Rule::new`](#method.new)). Used to point
+    /// diagnostics -- such as a token missing from the grammar -- at the rule's declaration.
+    pub name_span: Option<Span>
+}
+
+impl<StorageT> Rule<StorageT> {
+    /// Create a new `Rule`. An empty `start_states` means the rule has no `<...>` prefix (see
+    /// [`start_states`](#structfield.start_states)).
+    pub fn new(
+        tok_id: Option<StorageT>,
+        name: Option<String>,
+        re_str: String,
+        start_states: Vec<StartStateId>,
+        start_state_op: Option<StartStateOperation>
+    ) -> Result<Rule<StorageT>, Box<dyn Error>> {
+        Ok(Rule {
+            tok_id,
+            name,
+            re_str,
+            start_states,
+            start_state_op,
+            name_span: None
+        })
+    }
+
+    /// Does this rule fire in `start_states`'s `INITIAL` state (i.e. does it have no explicit
+    /// `<...>` prefix)?
+    pub fn is_initial_only(&self) -> bool {
+        self.start_states.is_empty()
+    }
+}
+
+/// The parsed, validated contents of a `.l` file.
+pub trait LexerDef<StorageT> {
+    /// Parse `s` (the contents of a `.l` file) into a `LexerDef`.
+    fn from_str(s: &str) -> Result<Self, LexBuildError>
+    where
+        Self: Sized;
+
+    /// Set this lexer's rule IDs from `rule_ids_map` (mapping rule name to token ID). Returns
+    /// (rules in the grammar but not the lexer, rules in the lexer but not the grammar).
+    fn set_rule_ids<'a>(
+        &'a mut self,
+        rule_ids_map: &HashMap<&'a str, StorageT>
+    ) -> (Option<Vec<&'a str>>, Option<Vec<&'a str>>)
+    where
+        StorageT: Copy;
+
+    /// An iterator over this `LexerDef`'s rules.
+    fn iter_rules(&self) -> Iter<Rule<StorageT>>;
+
+    /// The start states declared by this `LexerDef` (always includes `INITIAL` at index 0).
+    fn start_states(&self) -> &[StartState];
+}
+
+/// The various things that can go wrong when building a `.l` file into a `LexerDef`.
+#[derive(Debug)]
+pub enum LexBuildErrorKind {
+    PrematureEnd,
+    RoutinesNotSupported,
+    UnknownDeclaration,
+    MissingSpace,
+    InvalidStartStateName,
+    UnknownStartState,
+    DuplicateStartStateName,
+    RegexError
+}
+
+/// An error which occurred while building a `.l` file into a `LexerDef`, with the `Span` of
+/// source it occurred at so it can be rendered as a `line:col` diagnostic rather than a bare
+/// message.
+#[derive(Debug)]
+pub struct LexBuildError {
+    pub kind: LexBuildErrorKind,
+    pub span: Span
+}
+
+impl LexBuildError {
+    /// Render this error as a `line:col: message`, caret-underlined diagnostic against `src` (the
+    /// same string that was passed to [`LexerDef::from_str`](trait.LexerDef.html#tymethod.from_str)).
+    pub fn pp(&self, src: &str) -> String {
+        let cache = NewlineCache::new(src);
+        render(src, &cache, self.span, self)
+    }
+}
+
+impl fmt::Display for LexBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self.kind {
+            LexBuildErrorKind::PrematureEnd => "File ends prematurely",
+            LexBuildErrorKind::RoutinesNotSupported => "Routines not currently supported",
+            LexBuildErrorKind::UnknownDeclaration => "Unknown declaration",
+            LexBuildErrorKind::MissingSpace => "Rule is missing a space",
+            LexBuildErrorKind::InvalidStartStateName => "Invalid start state name",
+            LexBuildErrorKind::UnknownStartState => "Reference to unknown start state",
+            LexBuildErrorKind::DuplicateStartStateName => "Start state already defined",
+            LexBuildErrorKind::RegexError => "Invalid regular expression"
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Error for LexBuildError {}
+
+/// A non-streaming lexer: the whole `.l` file is parsed up front into a fixed list of `Rule`s
+/// and `StartState`s, which are then used, unchanged, for every subsequent lex.
+///
+/// `Serialize`/`Deserialize` let a statically generated lexer embed a pre-built `LexerDef` as a
+/// binary blob (see [`LexerBuilder`](../builder/struct.LexerBuilder.html)) rather than
+/// reconstructing every `Rule` from source literals at startup.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LRNonStreamingLexerDef<StorageT> {
+    rules: Vec<Rule<StorageT>>,
+    start_states: Vec<StartState>
+}
+
+impl<StorageT> LRNonStreamingLexerDef<StorageT> {
+    /// Create a new `LRNonStreamingLexerDef` directly from already-parsed `rules` and
+    /// `start_states`, without parsing a `.l` file.
+    pub fn from_rules(rules: Vec<Rule<StorageT>>, start_states: Vec<StartState>) -> Self {
+        LRNonStreamingLexerDef { rules, start_states }
+    }
+
+    /// The rules which are candidates for matching in `state`, in the order they should be
+    /// tried: `state`'s own rules (in declaration order), followed -- if `state` is inclusive --
+    /// by the rules with no `<...>` prefix (in declaration order; rules explicitly prefixed
+    /// with `<INITIAL>` are not among them). Each is paired with its index into
+    /// [`iter_rules`](trait.LexerDef.html#tymethod.iter_rules), so that a caller driving an
+    /// actual lex (e.g. [`LRNonStreamingLexer`](struct.LRNonStreamingLexer.html)) can look up the
+    /// regex it pre-compiled for that rule without re-scanning `self.rules`.
+    pub fn rules_for_state(&self, state: StartStateId) -> Vec<(usize, &Rule<StorageT>)> {
+        let mut out = Vec::new();
+        for (i, r) in self.rules.iter().enumerate() {
+            if r.start_states.contains(&state) {
+                out.push((i, r));
+            }
+        }
+        let inclusive = if state == INITIAL {
+            true
+        } else {
+            self.start_states
+                .iter()
+                .find(|ss| ss.id == state)
+                .map(|ss| !ss.exclusive)
+                .unwrap_or(true)
+        };
+        if inclusive {
+            for (i, r) in self.rules.iter().enumerate() {
+                if r.is_initial_only() {
+                    out.push((i, r));
+                }
+            }
+        }
+        out
+    }
+
+    /// Create a run-time [`LRNonStreamingLexer`](struct.LRNonStreamingLexer.html) which lexes
+    /// `s` against this `LexerDef`. Fails if any rule's `re_str` is not a valid regex (this can
+    /// only happen if `self` was built via [`from_rules`](#method.from_rules) with an invalid
+    /// pattern: rules parsed via [`from_str`](trait.LexerDef.html#tymethod.from_str) are already
+    /// validated at that point).
+    pub fn lexer<'a>(&'a self, s: &'a str) -> Result<LRNonStreamingLexer<'a, StorageT>, regex::Error> {
+        let regexes = self
+            .rules
+            .iter()
+            .map(|r| Regex::new(&format!("\\A(?:{})", r.re_str)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(LRNonStreamingLexer {
+            def: self,
+            regexes,
+            s,
+            off: 0,
+            state_stack: vec![INITIAL]
+        })
+    }
+}
+
+impl<StorageT> LexerDef<StorageT> for LRNonStreamingLexerDef<StorageT> {
+    fn from_str(s: &str) -> Result<Self, LexBuildError> {
+        parse(s)
+    }
+
+    fn set_rule_ids<'a>(
+        &'a mut self,
+        rule_ids_map: &HashMap<&'a str, StorageT>
+    ) -> (Option<Vec<&'a str>>, Option<Vec<&'a str>>)
+    where
+        StorageT: Copy
+    {
+        let mut missing_from_lexer = Vec::new();
+        let mut rule_names = Vec::new();
+        for r in &mut self.rules {
+            if let Some(ref n) = r.name {
+                rule_names.push(n.clone());
+                match rule_ids_map.get(n.as_str()) {
+                    Some(tok_id) => r.tok_id = Some(*tok_id),
+                    None => r.tok_id = None
+                }
+            }
+        }
+        for n in rule_ids_map.keys() {
+            if !rule_names.iter().any(|rn| rn == n) {
+                missing_from_lexer.push(*n);
+            }
+        }
+        let mut missing_from_parser = Vec::new();
+        for r in &self.rules {
+            if let Some(ref n) = r.name {
+                if !rule_ids_map.contains_key(n.as_str()) {
+                    missing_from_parser.push(n.as_str());
+                }
+            }
+        }
+        let mfl = if missing_from_lexer.is_empty() {
+            None
+        } else {
+            Some(missing_from_lexer)
+        };
+        let mfp = if missing_from_parser.is_empty() {
+            None
+        } else {
+            Some(missing_from_parser)
+        };
+        (mfl, mfp)
+    }
+
+    fn iter_rules(&self) -> Iter<Rule<StorageT>> {
+        self.rules.iter()
+    }
+
+    fn start_states(&self) -> &[StartState] {
+        &self.start_states
+    }
+}
+
+/// Parse the textual contents of a `.l` file into a `LRNonStreamingLexerDef`.
+///
+/// The header may declare exclusive (`%x NAME`) or inclusive (`%s NAME`) start states; each
+/// subsequent rule may be prefixed with `<name>` to restrict it to that start state (absent a
+/// prefix, a rule is active in `INITIAL` and in every inclusive start state). A rule's action may additionally be followed by
+/// `=> push NAME`, `=> pop`, or `=> NAME` to push, pop, or replace the start-state stack on a
+/// match.
+fn parse<StorageT>(s: &str) -> Result<LRNonStreamingLexerDef<StorageT>, LexBuildError> {
+    let cache = NewlineCache::new(s);
+    let mut start_states = vec![StartState::new(INITIAL, "INITIAL".to_string(), false)];
+    let mut rules = Vec::new();
+
+    let mut lines = s.lines().enumerate();
+    let mut in_header = true;
+    for (line_idx, line) in &mut lines {
+        let line_no = line_idx + 1;
+        let trimmed = line.trim();
+        if trimmed == "%%" {
+            in_header = false;
+            break;
+        }
+        if trimmed.is_empty() {
+            continue;
+        }
+        if let Some(rest) = trimmed.strip_prefix("%x").or_else(|| trimmed.strip_prefix("%s")) {
+            let exclusive = trimmed.starts_with("%x");
+            for name in rest.split_whitespace() {
+                if start_states.iter().any(|ss| ss.name == name) {
+                    return Err(LexBuildError {
+                        kind: LexBuildErrorKind::DuplicateStartStateName,
+                        span: cache.line_span(s, line_no)
+                    });
+                }
+                let id = StartStateId::new(start_states.len());
+                start_states.push(StartState::new(id, name.to_string(), exclusive));
+            }
+        } else {
+            return Err(LexBuildError {
+                kind: LexBuildErrorKind::UnknownDeclaration,
+                span: cache.line_span(s, line_no)
+            });
+        }
+    }
+    if in_header {
+        return Err(LexBuildError {
+            kind: LexBuildErrorKind::PrematureEnd,
+            span: cache.line_span(s, s.lines().count() + 1)
+        });
+    }
+
+    for (line_idx, line) in lines {
+        let line_no = line_idx + 1;
+        let rule_span = cache.line_span(s, line_no);
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if trimmed == "%%" {
+            // The (optional) user-code routines section: not currently supported.
+            return Err(LexBuildError {
+                kind: LexBuildErrorKind::RoutinesNotSupported,
+                span: rule_span
+            });
+        }
+
+        let (state_names, rest) = parse_state_prefix(trimmed, &cache, s, line_no)?;
+        let mut rule_start_states = Vec::with_capacity(state_names.len());
+        for name in &state_names {
+            match start_states.iter().find(|ss| &ss.name == name) {
+                Some(ss) => rule_start_states.push(ss.id),
+                None => {
+                    return Err(LexBuildError {
+                        kind: LexBuildErrorKind::UnknownStartState,
+                        span: rule_span
+                    })
+                }
+            }
+        }
+
+        let space_idx = rest.find(char::is_whitespace).ok_or(LexBuildError {
+            kind: LexBuildErrorKind::MissingSpace,
+            span: rule_span
+        })?;
+        let re_str = rest[..space_idx].to_string();
+        let action = rest[space_idx..].trim();
+
+        let (name, start_state_op) = parse_action(action, &start_states, &cache, s, line_no)?;
+
+        if Regex::new(&re_str).is_err() {
+            return Err(LexBuildError {
+                kind: LexBuildErrorKind::RegexError,
+                span: rule_span
+            });
+        }
+
+        rules.push(Rule {
+            tok_id: None,
+            name,
+            re_str,
+            start_states: rule_start_states,
+            start_state_op,
+            name_span: Some(rule_span)
+        });
+    }
+
+    Ok(LRNonStreamingLexerDef { rules, start_states })
+}
+
+/// Split a leading `<name1,name2>` prefix (if any) off `s`, returning the state names and the
+/// remainder of the line.
+fn parse_state_prefix<'a>(
+    s: &'a str,
+    cache: &NewlineCache,
+    src: &str,
+    line_no: usize
+) -> Result<(Vec<String>, &'a str), LexBuildError> {
+    if let Some(rest) = s.strip_prefix('<') {
+        match rest.find('>') {
+            Some(close) => {
+                let names = rest[..close]
+                    .split(',')
+                    .map(|n| n.trim().to_string())
+                    .filter(|n| !n.is_empty())
+                    .collect::<Vec<_>>();
+                if names.is_empty() {
+                    return Err(LexBuildError {
+                        kind: LexBuildErrorKind::InvalidStartStateName,
+                        span: cache.line_span(src, line_no)
+                    });
+                }
+                Ok((names, rest[close + 1..].trim_start()))
+            }
+            None => Err(LexBuildError {
+                kind: LexBuildErrorKind::InvalidStartStateName,
+                span: cache.line_span(src, line_no)
+            })
+        }
+    } else {
+        Ok((Vec::new(), s))
+    }
+}
+
+/// Parse a rule's action: the token name it produces, plus an optional `=> push X` / `=> pop` /
+/// `=> X` start-state operation.
+fn parse_action(
+    action: &str,
+    start_states: &[StartState],
+    cache: &NewlineCache,
+    src: &str,
+    line_no: usize
+) -> Result<(Option<String>, Option<StartStateOperation>), LexBuildError> {
+    let (name_part, op_part) = match action.find("=>") {
+        Some(idx) => (action[..idx].trim(), Some(action[idx + 2..].trim())),
+        None => (action.trim(), None)
+    };
+
+    let name = if name_part.is_empty() || name_part == ";" {
+        None
+    } else {
+        Some(name_part.trim_end_matches(';').to_string())
+    };
+
+    let op = match op_part {
+        None => None,
+        Some(op_str) => {
+            let op_str = op_str.trim_end_matches(';').trim();
+            if op_str == "pop" {
+                Some(StartStateOperation::Pop)
+            } else if let Some(target) = op_str.strip_prefix("push ") {
+                let id = lookup_state(start_states, target.trim(), cache, src, line_no)?;
+                Some(StartStateOperation::Push(id))
+            } else {
+                let id = lookup_state(start_states, op_str, cache, src, line_no)?;
+                Some(StartStateOperation::ReplaceStack(id))
+            }
+        }
+    };
+
+    Ok((name, op))
+}
+
+fn lookup_state(
+    start_states: &[StartState],
+    name: &str,
+    cache: &NewlineCache,
+    src: &str,
+    line_no: usize
+) -> Result<StartStateId, LexBuildError> {
+    start_states
+        .iter()
+        .find(|ss| ss.name == name)
+        .map(|ss| ss.id)
+        .ok_or(LexBuildError {
+            kind: LexBuildErrorKind::UnknownStartState,
+            span: cache.line_span(src, line_no)
+        })
+}
+
+/// A single token produced by lexing: `tok_id` identifies which [`Rule`](struct.Rule.html)
+/// matched, and `(start, len)` is its span in the input.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Lexeme<StorageT> {
+    pub tok_id: StorageT,
+    pub start: usize,
+    pub len: usize
+}
+
+impl<StorageT> Lexeme<StorageT> {
+    pub fn new(tok_id: StorageT, start: usize, len: usize) -> Self {
+        Lexeme { tok_id, start, len }
+    }
+
+    /// The byte range of this lexeme in the input it was lexed from.
+    pub fn span(&self) -> (usize, usize) {
+        (self.start, self.start + self.len)
+    }
+}
+
+/// No rule matched the input remaining at `off`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct LexError {
+    pub off: usize
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "No matching rule at byte offset {}", self.off)
+    }
+}
+
+impl Error for LexError {}
+
+/// A run-time, non-streaming lex of a single `&str`, driven by a
+/// [`LRNonStreamingLexerDef`](struct.LRNonStreamingLexerDef.html).
+///
+/// Created by [`LRNonStreamingLexerDef::lexer`](struct.LRNonStreamingLexerDef.html#method.lexer).
+/// Implements `Iterator`, yielding one [`Lexeme`](struct.Lexeme.html) per non-discarded match;
+/// rules whose `tok_id` is `None` (e.g. whitespace) are matched and skipped internally rather
+/// than yielded.
+///
+/// A stack of [`StartStateId`](struct.StartStateId.html)s is maintained at run-time: it starts
+/// as `[INITIAL]`, and is updated by each matched rule's
+/// [`StartStateOperation`](enum.StartStateOperation.html) (if any). At each position, only the
+/// rules [`rules_for_state`](struct.LRNonStreamingLexerDef.html#method.rules_for_state) returns
+/// for the state currently on top of the stack are tried; of those, the longest match wins,
+/// ties being broken in favour of the rule `rules_for_state` lists first (so a state's own
+/// rules beat equally long matches by rules with no `<...>` prefix). This is what allows e.g. nested comments or heredocs: a `<COMMENT>` start state whose
+/// rules only recognise `/*`, `*/`, and "anything else" effectively shadows the outer grammar
+/// until a matching `*/` pops back out of it.
+pub struct LRNonStreamingLexer<'a, StorageT> {
+    def: &'a LRNonStreamingLexerDef<StorageT>,
+    regexes: Vec<Regex>,
+    s: &'a str,
+    off: usize,
+    state_stack: Vec<StartStateId>
+}
+
+impl<'a, StorageT: Copy> LRNonStreamingLexer<'a, StorageT> {
+    /// The start state currently on top of the stack (`INITIAL` if, somehow, the stack has been
+    /// emptied).
+    pub fn current_state(&self) -> StartStateId {
+        *self.state_stack.last().unwrap_or(&INITIAL)
+    }
+
+    fn apply_op(&mut self, op: &StartStateOperation) {
+        match op {
+            StartStateOperation::Push(id) => self.state_stack.push(*id),
+            StartStateOperation::Pop => {
+                self.state_stack.pop();
+                if self.state_stack.is_empty() {
+                    self.state_stack.push(INITIAL);
+                }
+            }
+            StartStateOperation::ReplaceStack(id) => {
+                self.state_stack.clear();
+                self.state_stack.push(*id);
+            }
+        }
+    }
+}
+
+impl<'a, StorageT: Copy> Iterator for LRNonStreamingLexer<'a, StorageT> {
+    type Item = Result<Lexeme<StorageT>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.off >= self.s.len() {
+                return None;
+            }
+
+            let state = self.current_state();
+            let mut best: Option<(usize, usize)> = None; // (match len, rule idx)
+            for (idx, _) in self.def.rules_for_state(state) {
+                if let Some(m) = self.regexes[idx].find(&self.s[self.off..]) {
+                    let len = m.end();
+                    match best {
+                        Some((blen, _)) if len <= blen => {}
+                        _ => best = Some((len, idx))
+                    }
+                }
+            }
+
+            let (len, idx) = match best {
+                Some(b) => b,
+                None => return Some(Err(LexError { off: self.off }))
+            };
+
+            let rule = &self.def.rules[idx];
+            let start = self.off;
+            self.off += len;
+            if let Some(ref op) = rule.start_state_op {
+                self.apply_op(op);
+            }
+            if let Some(tok_id) = rule.tok_id {
+                return Some(Ok(Lexeme::new(tok_id, start, len)));
+            }
+            // `tok_id` is `None`: this rule matches but discards (e.g. whitespace). Keep
+            // scanning from the new offset rather than yielding anything for it.
+        }
+    }
+}
@@ -0,0 +1,12 @@
+//! `lrlex` compiles `.l` lexer specifications, either statically at build-time, or dynamically at
+//! run-time.
+
+mod builder;
+mod lexer;
+
+pub use builder::{LexerBuilder, LexerKind, RustEdition, Visibility};
+pub use lexer::{
+    LexBuildError, LexBuildErrorKind, LexError, Lexeme, LexerDef, LRNonStreamingLexer,
+    LRNonStreamingLexerDef, Rule, StartState, StartStateId, StartStateOperation
+};
+pub use cfgrammar::{NewlineCache, Span};
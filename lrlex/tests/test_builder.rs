@@ -0,0 +1,43 @@
+extern crate lrlex;
+
+use std::{
+    collections::HashMap,
+    fs,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use lrlex::LexerBuilder;
+
+#[test]
+fn test_mtime_shortcut_is_invalidated_by_a_changed_rule_ids_map() {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let dir = std::env::temp_dir().join(format!("lrlex_builder_test_{}_{}", std::process::id(), nanos));
+    fs::create_dir_all(&dir).unwrap();
+    let inp = dir.join("test.l");
+    let outp = dir.join("test.l.rs");
+    fs::write(&inp, "%%\n[a-z]+ ID\n").unwrap();
+
+    let mut rim1 = HashMap::new();
+    rim1.insert("ID".to_string(), 0u32);
+    let (missing_from_lexer, missing_from_parser) = LexerBuilder::<u32>::new()
+        .rule_ids_map(rim1)
+        .process_file(&inp, &outp)
+        .unwrap();
+    assert_eq!(missing_from_lexer, None);
+    assert_eq!(missing_from_parser, None);
+
+    // `inp` is untouched, so `outp` is still newer than it -- but the rule-ID map changed (as if
+    // a paired `.y` grammar had stopped mentioning `ID`), so the mtime short-circuit must not
+    // kick in and hide that from the caller.
+    let rim2: HashMap<String, u32> = HashMap::new();
+    let (missing_from_lexer2, missing_from_parser2) = LexerBuilder::<u32>::new()
+        .rule_ids_map(rim2)
+        .process_file(&inp, &outp)
+        .unwrap();
+    let mut expected_missing = std::collections::HashSet::new();
+    expected_missing.insert("ID".to_string());
+    assert_eq!(missing_from_lexer2, None);
+    assert_eq!(missing_from_parser2, Some(expected_missing));
+
+    fs::remove_dir_all(&dir).ok();
+}
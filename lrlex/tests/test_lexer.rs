@@ -0,0 +1,62 @@
+extern crate lrlex;
+
+use std::collections::HashMap;
+
+use lrlex::{LexBuildErrorKind, LexerDef, LRNonStreamingLexerDef};
+
+#[test]
+fn test_start_state_push_pop() {
+    let src = "
+        %x STR
+        %%
+        [a-z]+ ID
+        \" ; => push STR
+        <STR>[^\"]+ STRTEXT
+        <STR>\" ; => pop
+    ";
+    let mut lexerdef = LRNonStreamingLexerDef::<u32>::from_str(src).unwrap();
+    let mut rim = HashMap::new();
+    rim.insert("ID", 0u32);
+    rim.insert("STRTEXT", 1u32);
+    lexerdef.set_rule_ids(&rim);
+
+    let input = "abc\"hello\"def";
+    let lexer = lexerdef.lexer(input).unwrap();
+    let toks = lexer.collect::<Result<Vec<_>, _>>().unwrap();
+    let texts = toks
+        .iter()
+        .map(|l| {
+            let (s, e) = l.span();
+            &input[s..e]
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(texts, vec!["abc", "hello", "def"]);
+    assert_eq!(toks[0].tok_id, 0);
+    assert_eq!(toks[1].tok_id, 1);
+    assert_eq!(toks[2].tok_id, 0);
+}
+
+#[test]
+fn test_invalid_regex_is_rejected() {
+    let src = "
+        %%
+        [a-z+ ID
+    ";
+    let err = LRNonStreamingLexerDef::<u32>::from_str(src).unwrap_err();
+    match err.kind {
+        LexBuildErrorKind::RegexError => (),
+        ref other => panic!("expected RegexError, got {:?}", other)
+    }
+}
+
+#[test]
+fn test_no_match_is_an_error() {
+    let src = "
+        %%
+        [a-z]+ ID
+    ";
+    let lexerdef = LRNonStreamingLexerDef::<u32>::from_str(src).unwrap();
+    let mut lexer = lexerdef.lexer("abc123").unwrap();
+    assert!(lexer.next().unwrap().is_ok());
+    assert!(lexer.next().unwrap().is_err());
+}
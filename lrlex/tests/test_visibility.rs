@@ -0,0 +1,50 @@
+extern crate lrlex;
+
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+use lrlex::{LexerBuilder, RustEdition, Visibility};
+
+fn tmp_dir(tag: &str) -> std::path::PathBuf {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let dir = std::env::temp_dir().join(format!("lrlex_visibility_test_{}_{}_{}", tag, std::process::id(), nanos));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_default_visibility_and_edition() {
+    let dir = tmp_dir("default");
+    let inp = dir.join("test.l");
+    let outp = dir.join("test.l.rs");
+    fs::write(&inp, "%%\n[a-z]+ ID\n").unwrap();
+
+    LexerBuilder::<u32>::new().process_file(&inp, &outp).unwrap();
+    let generated = fs::read_to_string(&outp).unwrap();
+    assert!(generated.contains("pub mod"));
+    assert!(!generated.contains("extern crate"));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_pub_crate_visibility_and_rust_2015_needs_extern_crate() {
+    let dir = tmp_dir("pub_crate_2015");
+    let inp = dir.join("test.l");
+    let outp = dir.join("test.l.rs");
+    fs::write(&inp, "%%\n[a-z]+ ID\n").unwrap();
+
+    LexerBuilder::<u32>::new()
+        .visibility(Visibility::PublicCrate)
+        .rust_edition(RustEdition::Rust2015)
+        .process_file(&inp, &outp)
+        .unwrap();
+    let generated = fs::read_to_string(&outp).unwrap();
+    assert!(generated.contains("pub(crate) mod"));
+    assert!(generated.contains("extern crate bincode;"));
+    assert!(generated.contains("extern crate lrlex;"));
+
+    fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,75 @@
+//! A cactus (a.k.a. "spaghetti") stack: an immutable, singly-linked stack where nodes can be
+//! cheaply shared between multiple logical stacks. Pushing returns a new stack sharing the tail
+//! of the old one; cloning is an `Rc` bump. This is what lets the error-recovery search explore
+//! many divergent parser-stack configurations without repeatedly copying the whole stack.
+
+use std::rc::Rc;
+
+#[derive(Debug)]
+struct Node<T> {
+    val: T,
+    parent: Option<Rc<Node<T>>>
+}
+
+/// An immutable stack which can be cheaply cloned, and whose prefixes can be shared between
+/// multiple logical stacks.
+#[derive(Debug)]
+pub struct Cactus<T> {
+    node: Option<Rc<Node<T>>>
+}
+
+impl<T> Clone for Cactus<T> {
+    fn clone(&self) -> Self {
+        Cactus { node: self.node.clone() }
+    }
+}
+
+impl<T> Cactus<T> {
+    /// Create an empty stack.
+    pub fn new() -> Self {
+        Cactus { node: None }
+    }
+
+    /// Is this stack empty?
+    pub fn is_empty(&self) -> bool {
+        self.node.is_none()
+    }
+
+    /// Push `val` on top of this stack, returning a new stack. `self` is untouched, and may
+    /// continue to be used (e.g. as the tail of another, divergent, push).
+    pub fn push(&self, val: T) -> Self {
+        Cactus {
+            node: Some(Rc::new(Node { val, parent: self.node.clone() }))
+        }
+    }
+
+    /// Return a new stack with the top value popped off, and the popped value (if any).
+    pub fn pop(&self) -> (Self, Option<&T>) {
+        match self.node {
+            Some(ref n) => (Cactus { node: n.parent.clone() }, Some(&n.val)),
+            None => (Cactus { node: None }, None)
+        }
+    }
+
+    /// A reference to the value on top of the stack, if any.
+    pub fn peek(&self) -> Option<&T> {
+        self.node.as_ref().map(|n| &n.val)
+    }
+
+    /// The number of elements in this stack.
+    pub fn len(&self) -> usize {
+        let mut n = 0;
+        let mut cur = &self.node;
+        while let Some(ref node) = cur {
+            n += 1;
+            cur = &node.parent;
+        }
+        n
+    }
+}
+
+impl<T> Default for Cactus<T> {
+    fn default() -> Self {
+        Cactus::new()
+    }
+}
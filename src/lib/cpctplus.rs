@@ -0,0 +1,261 @@
+//! The CPCT+ minimum-cost error-recovery algorithm.
+//!
+//! On hitting an LR action error, we search for the cheapest sequence of edits -- inserting a
+//! terminal, deleting the next input token, or shifting the next input token -- which lets
+//! parsing continue. The search proceeds by increasing cost (a Dijkstra/BFS-by-cost over
+//! configurations of "parser stack + input offset"), and a configuration counts as recovered
+//! once it has shifted [`SHIFTS_TO_RECOVER`](constant.SHIFTS_TO_RECOVER.html) real input tokens
+//! past the error. All minimal-cost repair sequences are returned (not just one), so that
+//! ambiguous fixes can be reported to the user; the caller is expected to apply the first one
+//! and continue parsing.
+//!
+//! Inserting a terminal considers not just states that can shift it directly, but also states
+//! that reduce on it first (via [`RecoveryTable::reduce`](trait.RecoveryTable.html#tymethod.reduce))
+//! and can then shift the result -- the usual case in real LR automata, where most states reach
+//! a shift only after one or more reduces.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    hash::Hash,
+    time::{Duration, Instant}
+};
+
+use crate::cactus::Cactus;
+
+/// The number of real input tokens a candidate repair sequence must shift before it is
+/// considered to have recovered from the error.
+pub const SHIFTS_TO_RECOVER: u32 = 3;
+
+/// The default wall-clock budget given to the search before it gives up and falls back to
+/// panic-mode recovery.
+pub const DEFAULT_RECOVERY_BUDGET: Duration = Duration::from_millis(500);
+
+/// A single edit applied during error recovery.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseRepair<StorageT> {
+    /// Insert a (synthetic) token of this type.
+    Insert(StorageT),
+    /// Delete the next token in the input.
+    Delete,
+    /// Accept (shift) the next token in the input as-is.
+    Shift
+}
+
+impl<StorageT> ParseRepair<StorageT> {
+    fn cost(&self) -> u32 {
+        match self {
+            ParseRepair::Insert(_) | ParseRepair::Delete => 1,
+            ParseRepair::Shift => 0
+        }
+    }
+}
+
+/// The operations a recovery search needs from whatever LR table it's recovering against. This
+/// is deliberately minimal: it lets `recover` stay generic over the concrete state-table/grammar
+/// representation the rest of the parser uses.
+pub trait RecoveryTable<StorageT: Copy + Eq + Hash> {
+    /// The type used to identify a state in the LR automaton.
+    type StateIdx: Copy + Eq;
+
+    /// If `tok` can be shifted while `state` is on top of the stack, the state reached by doing
+    /// so.
+    fn shift(&self, state: Self::StateIdx, tok: StorageT) -> Option<Self::StateIdx>;
+
+    /// If `tok` is in `state`'s reduce lookahead set, the number of stack entries the reduce(s)
+    /// pop and the state reached once they have been popped and the resulting nonterminal's
+    /// `goto` pushed (i.e. the state from which `tok` should then be tried again, typically via
+    /// [`shift`](#tymethod.shift)). A single entry here may stand for a single reduce, or a
+    /// single entry that itself triggers further reduces: either way, the caller only needs to
+    /// know how many cactus-stack frames to pop and what replaces them.
+    fn reduce(&self, state: Self::StateIdx, tok: StorageT) -> Option<(usize, Self::StateIdx)>;
+
+    /// All the terminals which could usefully be inserted while `state` is on top of the stack
+    /// (i.e. the terminals on which this state can shift or reduce).
+    fn insertable(&self, state: Self::StateIdx) -> Vec<StorageT>;
+}
+
+/// Pop `n` frames off `c`, returning the resulting stack (e.g. to reveal the state a reduce's
+/// `goto` should be applied to).
+fn pop_n<StateIdx>(mut c: Cactus<StateIdx>, n: usize) -> Cactus<StateIdx> {
+    for _ in 0..n {
+        c = c.pop().0;
+    }
+    c
+}
+
+#[derive(Clone)]
+struct Config<StorageT, StateIdx> {
+    pstack: Cactus<StateIdx>,
+    input_off: usize,
+    shifts: u32,
+    cost: u32,
+    repairs: Vec<ParseRepair<StorageT>>
+}
+
+impl<StorageT, StateIdx> PartialEq for Config<StorageT, StateIdx> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+impl<StorageT, StateIdx> Eq for Config<StorageT, StateIdx> {}
+impl<StorageT, StateIdx> PartialOrd for Config<StorageT, StateIdx> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<StorageT, StateIdx> Ord for Config<StorageT, StateIdx> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost.cmp(&other.cost)
+    }
+}
+
+/// Search for the minimum-cost repair sequence(s) which let parsing continue from `pstack` (the
+/// LR state stack at the point of the error) with `input[input_off..]` remaining to be consumed.
+///
+/// Returns every repair sequence of minimal cost (there may be more than one, if several
+/// different edits are equally cheap), merging duplicates. If the search exceeds `budget` before
+/// finding any recovered configuration, or runs out of configurations without finding one,
+/// falls back to panic-mode recovery: deleting input tokens
+/// one at a time until one is found on which the current state can shift.
+pub fn recover<T, StorageT>(
+    table: &T,
+    pstack: Cactus<T::StateIdx>,
+    input: &[StorageT],
+    input_off: usize,
+    budget: Duration
+) -> Vec<Vec<ParseRepair<StorageT>>>
+where
+    T: RecoveryTable<StorageT>,
+    StorageT: Copy + Eq + Hash
+{
+    let start_state = *pstack.peek().expect("recovery requires a non-empty stack");
+    let start = Instant::now();
+    let mut heap = BinaryHeap::new();
+    heap.push(Reverse(Config {
+        pstack,
+        input_off,
+        shifts: 0,
+        cost: 0,
+        repairs: Vec::new()
+    }));
+
+    let mut found: Vec<Vec<ParseRepair<StorageT>>> = Vec::new();
+    let mut found_cost = None;
+
+    while let Some(Reverse(cur)) = heap.pop() {
+        if start.elapsed() > budget && found.is_empty() {
+            return panic_mode(table, start_state, input, input_off);
+        }
+        if let Some(fc) = found_cost {
+            if cur.cost > fc {
+                break;
+            }
+        }
+        if cur.shifts >= SHIFTS_TO_RECOVER {
+            if !found.contains(&cur.repairs) {
+                found.push(cur.repairs);
+                found_cost = Some(cur.cost);
+            }
+            continue;
+        }
+
+        let state = *cur.pstack.peek().unwrap();
+
+        // (a) Insert any terminal this state can shift/reduce on.
+        for tok in table.insertable(state) {
+            if let Some(new_state) = table.shift(state, tok) {
+                let mut repairs = cur.repairs.clone();
+                repairs.push(ParseRepair::Insert(tok));
+                heap.push(Reverse(Config {
+                    pstack: cur.pstack.push(new_state),
+                    input_off: cur.input_off,
+                    shifts: cur.shifts,
+                    cost: cur.cost + 1,
+                    repairs
+                }));
+            } else if let Some((pop, goto_state)) = table.reduce(state, tok) {
+                // `tok` can't be shifted directly, but this state can reduce on it first: pop
+                // the reduce's symbols off the stack, push the resulting nonterminal's `goto`,
+                // then try shifting `tok` again from there.
+                let reduced = pop_n(cur.pstack.clone(), pop).push(goto_state);
+                if let Some(new_state) = table.shift(goto_state, tok) {
+                    let mut repairs = cur.repairs.clone();
+                    repairs.push(ParseRepair::Insert(tok));
+                    heap.push(Reverse(Config {
+                        pstack: reduced.push(new_state),
+                        input_off: cur.input_off,
+                        shifts: cur.shifts,
+                        cost: cur.cost + 1,
+                        repairs
+                    }));
+                }
+            }
+        }
+
+        // (b) Delete the next input token.
+        if cur.input_off < input.len() {
+            let mut repairs = cur.repairs.clone();
+            repairs.push(ParseRepair::Delete);
+            heap.push(Reverse(Config {
+                pstack: cur.pstack.clone(),
+                input_off: cur.input_off + 1,
+                shifts: cur.shifts,
+                cost: cur.cost + 1,
+                repairs
+            }));
+        }
+
+        // (c) Shift the next input token, if possible.
+        if cur.input_off < input.len() {
+            let tok = input[cur.input_off];
+            if let Some(new_state) = table.shift(state, tok) {
+                let mut repairs = cur.repairs.clone();
+                repairs.push(ParseRepair::Shift);
+                heap.push(Reverse(Config {
+                    pstack: cur.pstack.push(new_state),
+                    input_off: cur.input_off + 1,
+                    shifts: cur.shifts + 1,
+                    cost: cur.cost,
+                    repairs
+                }));
+            }
+        }
+    }
+
+    if found.is_empty() {
+        return panic_mode(table, start_state, input, input_off);
+    }
+    found
+}
+
+/// Panic-mode recovery: skip input tokens one at a time until one is found on which `state` can
+/// shift (or the input is exhausted). Used when the CPCT+ search exceeds its time budget, or
+/// runs out of configurations to try, without finding a recovery.
+fn panic_mode<T, StorageT>(
+    table: &T,
+    state: T::StateIdx,
+    input: &[StorageT],
+    input_off: usize
+) -> Vec<Vec<ParseRepair<StorageT>>>
+where
+    T: RecoveryTable<StorageT>,
+    StorageT: Copy + Eq + Hash
+{
+    let mut repairs = Vec::new();
+    let mut off = input_off;
+    while off < input.len() {
+        if table.shift(state, input[off]).is_some() {
+            repairs.push(ParseRepair::Shift);
+            return vec![repairs];
+        }
+        repairs.push(ParseRepair::Delete);
+        off += 1;
+    }
+    vec![repairs]
+}
+
+#[allow(dead_code)]
+fn total_cost<StorageT>(repairs: &[ParseRepair<StorageT>]) -> u32 {
+    repairs.iter().map(ParseRepair::cost).sum()
+}
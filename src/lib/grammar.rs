@@ -0,0 +1,24 @@
+//! Turn a validated [`GrammarAST`](../grammar_ast/struct.GrammarAST.html) into the flattened
+//! `Grammar` representation the rest of the parser-generation pipeline works with.
+
+use crate::grammar_ast::GrammarAST;
+
+/// A grammar's rules and productions, flattened out of their AST form ready for table
+/// generation.
+#[derive(Clone, Debug)]
+pub struct Grammar {
+    pub start: Option<String>,
+    pub rule_names: Vec<String>
+}
+
+/// Flatten `ast` (which should already have been through
+/// [`GrammarAST::validate`](../grammar_ast/struct.GrammarAST.html#method.validate)) into a
+/// `Grammar`.
+pub fn ast_to_grammar(ast: &GrammarAST) -> Grammar {
+    let mut rule_names = ast.rules.keys().cloned().collect::<Vec<_>>();
+    rule_names.sort();
+    Grammar {
+        start: ast.start.clone(),
+        rule_names
+    }
+}
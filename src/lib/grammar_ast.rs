@@ -0,0 +1,112 @@
+//! The abstract syntax tree produced by parsing a Yacc grammar: rules, their alternative
+//! productions, and the declarations (`%start`, `%token`) that apply to the grammar as a whole.
+
+use std::{collections::{HashMap, HashSet}, fmt};
+
+/// A single symbol in a rule's production: either a reference to another rule (`Nonterminal`) or
+/// a token produced by the lexer (`Terminal`).
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Symbol {
+    Nonterminal(String),
+    Terminal(String)
+}
+
+/// Convenience constructor for `Symbol::Nonterminal`.
+pub fn nonterminal<T: Into<String>>(name: T) -> Symbol {
+    Symbol::Nonterminal(name.into())
+}
+
+/// Convenience constructor for `Symbol::Terminal`.
+pub fn terminal<T: Into<String>>(name: T) -> Symbol {
+    Symbol::Terminal(name.into())
+}
+
+/// A grammar rule: a nonterminal name plus every alternative production it can expand to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub alternatives: Vec<Vec<Symbol>>
+}
+
+impl Rule {
+    pub fn new(name: String) -> Self {
+        Rule { name, alternatives: Vec::new() }
+    }
+
+    /// Add a single alternative production (a sequence of symbols) to this rule.
+    pub fn add_symbols(&mut self, symbols: Vec<Symbol>) {
+        self.alternatives.push(symbols);
+    }
+}
+
+/// What can go wrong when validating a parsed `GrammarAST`.
+#[derive(Debug, Eq, PartialEq)]
+pub enum GrammarASTError {
+    /// `%start` named a nonterminal with no corresponding rule.
+    UnknownStartRule(String),
+    /// A production referenced a nonterminal with no corresponding rule.
+    UnknownNonterminal(String)
+}
+
+impl fmt::Display for GrammarASTError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GrammarASTError::UnknownStartRule(ref n) => {
+                write!(f, "Unknown start rule '{}'", n)
+            }
+            GrammarASTError::UnknownNonterminal(ref n) => {
+                write!(f, "Unknown nonterminal '{}'", n)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GrammarASTError {}
+
+/// The parsed, but not yet validated, contents of a `.y` file.
+#[derive(Clone, Debug, Default)]
+pub struct GrammarAST {
+    pub start: Option<String>,
+    pub tokens: HashSet<String>,
+    pub rules: HashMap<String, Rule>
+}
+
+impl GrammarAST {
+    pub fn new() -> Self {
+        GrammarAST {
+            start: None,
+            tokens: HashSet::new(),
+            rules: HashMap::new()
+        }
+    }
+
+    pub fn has_token(&self, name: &str) -> bool {
+        self.tokens.contains(name)
+    }
+
+    pub fn get_rule(&self, name: &str) -> Option<&Rule> {
+        self.rules.get(name)
+    }
+
+    /// Check the grammar's internal consistency: that `%start` (if any) names a real rule, and
+    /// that every nonterminal referenced in a production has a corresponding rule.
+    pub fn validate(&self) -> Result<(), GrammarASTError> {
+        if let Some(ref start) = self.start {
+            if !self.rules.contains_key(start) {
+                return Err(GrammarASTError::UnknownStartRule(start.clone()));
+            }
+        }
+        for rule in self.rules.values() {
+            for alt in &rule.alternatives {
+                for sym in alt {
+                    if let Symbol::Nonterminal(ref n) = sym {
+                        if !self.rules.contains_key(n) {
+                            return Err(GrammarASTError::UnknownNonterminal(n.clone()));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -6,9 +6,16 @@ pub mod grammar;
 pub mod grammar_ast;
 pub mod yacc;
 
+pub mod cactus;
+pub mod cpctplus;
+pub mod node;
+pub mod parser;
 pub mod pgen;
 pub use grammar::ast_to_grammar;
 pub use grammar_ast::{GrammarAST, GrammarASTError};
+pub use self::node::{Lexeme, Node};
+pub use self::parser::{ActionKind, ParseError, RTParserBuilder, RecoveryKind};
+pub use cfgrammar::{NewlineCache, Span};
 pub use self::yacc::{YaccError, YaccErrorKind};
 use self::yacc::parse_yacc;
 
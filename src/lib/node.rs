@@ -0,0 +1,72 @@
+//! A generic parse tree: useful when all you want is "give me the tree", without having to write
+//! any grammar actions at all.
+
+use std::fmt::Write as _;
+
+use crate::grammar::Grammar;
+
+/// A single token matched by the lexer: `tok_id` identifies which terminal it is, and
+/// `(start, len)` is its span in the original input.
+#[derive(Clone, Copy, Debug)]
+pub struct Lexeme<StorageT> {
+    pub tok_id: StorageT,
+    pub start: usize,
+    pub len: usize
+}
+
+impl<StorageT> Lexeme<StorageT> {
+    pub fn new(tok_id: StorageT, start: usize, len: usize) -> Self {
+        Lexeme { tok_id, start, len }
+    }
+
+    /// The byte range of this lexeme in the input it was lexed from.
+    pub fn span(&self) -> (usize, usize) {
+        (self.start, self.start + self.len)
+    }
+}
+
+/// A node in a generic parse tree, parameterised over the storage type used for token/rule
+/// indices: a leaf (`Term`) wraps the `Lexeme` the lexer produced, and an interior node
+/// (`Nonterm`) records which rule (`ridx`, an index into
+/// [`Grammar::rule_names`](../grammar/struct.Grammar.html)) was reduced and the child nodes of
+/// its production.
+#[derive(Clone, Debug)]
+pub enum Node<StorageT> {
+    Term { lexeme: Lexeme<StorageT> },
+    Nonterm { ridx: usize, nodes: Vec<Node<StorageT>> }
+}
+
+impl<StorageT> Node<StorageT> {
+    /// Render this tree as an indented, multi-line string: each nonterminal is printed as its
+    /// rule name, each terminal as the source substring its lexeme covers.
+    ///
+    /// Deep parse trees are not uncommon (e.g. long lists parsed via left recursion), so this
+    /// walks the tree with an explicit work stack of `(indent_level, node)` pairs rather than
+    /// recursing, to avoid blowing the native stack.
+    pub fn pp(&self, grm: &Grammar, input: &str) -> String {
+        let mut out = String::new();
+        let mut stack: Vec<(usize, &Node<StorageT>)> = vec![(0, self)];
+        while let Some((indent, node)) = stack.pop() {
+            match node {
+                Node::Term { lexeme } => {
+                    let (start, end) = lexeme.span();
+                    writeln!(out, "{}{}", "  ".repeat(indent), &input[start..end]).ok();
+                }
+                Node::Nonterm { ridx, nodes } => {
+                    let name = grm
+                        .rule_names
+                        .get(*ridx)
+                        .map(String::as_str)
+                        .unwrap_or("<unknown rule>");
+                    writeln!(out, "{}{}", "  ".repeat(indent), name).ok();
+                    // Pushed in reverse so that, since we pop from the back, children are
+                    // visited (and thus printed) in their original left-to-right order.
+                    for n in nodes.iter().rev() {
+                        stack.push((indent + 1, n));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,219 @@
+//! Run-time parsing support: driving the LR tables produced from a grammar, and (optionally)
+//! recovering from syntax errors rather than giving up at the first one.
+
+use std::time::Duration;
+
+use crate::cactus::Cactus;
+use crate::cpctplus::{self, ParseRepair, RecoveryTable, DEFAULT_RECOVERY_BUDGET};
+use crate::node::{Lexeme, Node};
+
+/// Which error-recovery algorithm (if any) a [`RTParserBuilder`](struct.RTParserBuilder.html)
+/// should use.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RecoveryKind {
+    /// Stop at the first parse error (the traditional behaviour).
+    None,
+    /// Use the CPCT+ minimum-cost repair-sequence algorithm.
+    CPCTPlus
+}
+
+impl Default for RecoveryKind {
+    fn default() -> Self {
+        RecoveryKind::CPCTPlus
+    }
+}
+
+/// Which actions a [`RTParserBuilder`](struct.RTParserBuilder.html)-configured parser runs on
+/// each reduction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ActionKind {
+    /// Run the grammar's user-supplied actions (the traditional behaviour).
+    UserAction,
+    /// Ignore any user actions and instead build a generic
+    /// [`Node`](../node/enum.Node.html) parse tree automatically, one per reduction/shift. Useful
+    /// for tooling, debugging, and teaching, when all you want is "give me the tree".
+    GenericParseTree
+}
+
+impl Default for ActionKind {
+    fn default() -> Self {
+        ActionKind::UserAction
+    }
+}
+
+/// A report of a single error encountered (and, if recovery was enabled, repaired) during
+/// parsing.
+#[derive(Clone, Debug)]
+pub struct ParseError<StorageT> {
+    /// The input offset (in tokens) at which the error was detected.
+    pub input_off: usize,
+    /// Every minimal-cost repair sequence which would let parsing continue from here. The first
+    /// is the one actually applied.
+    pub repairs: Vec<Vec<ParseRepair<StorageT>>>
+}
+
+/// Configures how a statically- or dynamically-generated parser behaves at run-time.
+pub struct RTParserBuilder {
+    recovery_kind: RecoveryKind,
+    recovery_budget: Duration,
+    action_kind: ActionKind
+}
+
+impl RTParserBuilder {
+    /// Create a new `RTParserBuilder` with error recovery enabled (using CPCT+), the default
+    /// recovery time budget, and user actions enabled.
+    pub fn new() -> Self {
+        RTParserBuilder {
+            recovery_kind: RecoveryKind::default(),
+            recovery_budget: DEFAULT_RECOVERY_BUDGET,
+            action_kind: ActionKind::default()
+        }
+    }
+
+    /// Set the error-recovery algorithm to use.
+    pub fn recoverer(mut self, rk: RecoveryKind) -> Self {
+        self.recovery_kind = rk;
+        self
+    }
+
+    /// Set the wall-clock budget given to the recovery search before it falls back to
+    /// panic-mode token skipping. Defaults to 500ms.
+    pub fn recovery_budget(mut self, budget: Duration) -> Self {
+        self.recovery_budget = budget;
+        self
+    }
+
+    /// Set which actions the parser runs on each reduction. Defaults to
+    /// [`ActionKind::UserAction`](enum.ActionKind.html).
+    pub fn actioner(mut self, ak: ActionKind) -> Self {
+        self.action_kind = ak;
+        self
+    }
+
+    pub fn recovery_kind(&self) -> RecoveryKind {
+        self.recovery_kind
+    }
+
+    pub fn action_kind(&self) -> ActionKind {
+        self.action_kind
+    }
+
+    /// When [`action_kind`](#method.action_kind) is
+    /// [`ActionKind::GenericParseTree`](enum.ActionKind.html), a driver calls this on every
+    /// shift, instead of running the grammar's user action, to build the leaf node for the
+    /// shifted lexeme. Panics if `action_kind` is `ActionKind::UserAction`.
+    pub fn term_node<StorageT>(&self, lexeme: Lexeme<StorageT>) -> Node<StorageT> {
+        assert_eq!(self.action_kind, ActionKind::GenericParseTree);
+        Node::Term { lexeme }
+    }
+
+    /// As [`term_node`](#method.term_node), but called on every reduce to build the interior
+    /// node for `ridx`'s production from its already-built child nodes.
+    pub fn nonterm_node<StorageT>(&self, ridx: usize, nodes: Vec<Node<StorageT>>) -> Node<StorageT> {
+        assert_eq!(self.action_kind, ActionKind::GenericParseTree);
+        Node::Nonterm { ridx, nodes }
+    }
+}
+
+impl Default for RTParserBuilder {
+    fn default() -> Self {
+        RTParserBuilder::new()
+    }
+}
+
+/// Parse `input` against `table`, starting in `table`'s start state. On success, returns
+/// `Ok(())`; on one or more parse errors, if recovery is enabled, each is repaired in turn (using
+/// the first of its minimal-cost repair sequences) and parsing resumes, with every error (and
+/// the full set of minimal-cost repairs considered for it) returned in `Err`.
+///
+/// This only drives recovery itself: shifting/reducing the non-error-case grammar symbols is the
+/// responsibility of the table-specific driver that calls into this, since that requires the
+/// full LR state/production tables which are out of scope here. `error_offs` must therefore
+/// still be supplied by that driver rather than discovered here; what this function does
+/// guarantee is that each successive error is recovered from the parser stack the *previous*
+/// repair actually left behind, by replaying its first (applied) repair sequence against
+/// `table`, rather than resetting to the start state as if no earlier error had been repaired.
+pub fn parse_with_recovery<T, StorageT>(
+    table: &T,
+    start: T::StateIdx,
+    input: &[StorageT],
+    error_offs: &[usize],
+    builder: &RTParserBuilder
+) -> Result<(), Vec<ParseError<StorageT>>>
+where
+    T: RecoveryTable<StorageT>,
+    StorageT: Copy + Eq + std::hash::Hash
+{
+    if builder.recovery_kind == RecoveryKind::None || error_offs.is_empty() {
+        return if error_offs.is_empty() {
+            Ok(())
+        } else {
+            Err(error_offs
+                .iter()
+                .take(1)
+                .map(|&input_off| ParseError { input_off, repairs: Vec::new() })
+                .collect())
+        };
+    }
+
+    let mut errs = Vec::new();
+    let mut pstack = Cactus::new().push(start);
+    for &input_off in error_offs {
+        let repairs = cpctplus::recover(table, pstack.clone(), input, input_off, builder.recovery_budget);
+        if let Some(applied) = repairs.first() {
+            pstack = apply_repairs(table, pstack, applied, input, input_off);
+        }
+        errs.push(ParseError { input_off, repairs });
+    }
+    Err(errs)
+}
+
+/// Replay `repairs` (the repair sequence actually applied, i.e. the first of those
+/// [`cpctplus::recover`](../cpctplus/fn.recover.html) found) against `pstack`, returning the
+/// stack it leaves behind.
+fn apply_repairs<T, StorageT>(
+    table: &T,
+    pstack: Cactus<T::StateIdx>,
+    repairs: &[ParseRepair<StorageT>],
+    input: &[StorageT],
+    input_off: usize
+) -> Cactus<T::StateIdx>
+where
+    T: RecoveryTable<StorageT>,
+    StorageT: Copy + Eq + std::hash::Hash
+{
+    let mut pstack = pstack;
+    let mut off = input_off;
+    for repair in repairs {
+        match repair {
+            ParseRepair::Insert(tok) => {
+                let state = *pstack.peek().expect("non-empty stack");
+                if let Some(new_state) = table.shift(state, *tok) {
+                    pstack = pstack.push(new_state);
+                } else if let Some((pop, goto_state)) = table.reduce(state, *tok) {
+                    let mut reduced = pstack;
+                    for _ in 0..pop {
+                        reduced = reduced.pop().0;
+                    }
+                    pstack = reduced.push(goto_state);
+                    if let Some(new_state) = table.shift(goto_state, *tok) {
+                        pstack = pstack.push(new_state);
+                    }
+                }
+            }
+            ParseRepair::Delete => {
+                off += 1;
+            }
+            ParseRepair::Shift => {
+                if off < input.len() {
+                    let state = *pstack.peek().expect("non-empty stack");
+                    if let Some(new_state) = table.shift(state, input[off]) {
+                        pstack = pstack.push(new_state);
+                    }
+                    off += 1;
+                }
+            }
+        }
+    }
+    pstack
+}
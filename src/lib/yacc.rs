@@ -0,0 +1,253 @@
+//! A hand-rolled recursive-descent parser for (a subset of) Yacc grammars.
+
+use std::{error::Error, fmt};
+
+use cfgrammar::{render, NewlineCache, Span};
+
+use crate::grammar_ast::{GrammarAST, Rule, Symbol};
+
+/// What went wrong while parsing a `.y` file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum YaccErrorKind {
+    /// A rule's production wasn't terminated with a `;` before the file ended.
+    IncompleteRule,
+    /// A rule name wasn't followed by a `:`.
+    MissingColon,
+    /// The file ended before the `%%` marking the start of the rules section was found.
+    PrematureEnd,
+    /// A second `%%` was followed by a non-empty "programs" section, which isn't supported.
+    ProgramsNotSupported,
+    /// A `%foo` declaration that isn't recognised.
+    UnknownDeclaration
+}
+
+/// An error encountered while parsing a `.y` file, with the `Span` of source it occurred at so
+/// that it can be rendered as a `line:col` diagnostic rather than a bare message.
+#[derive(Clone, Debug)]
+pub struct YaccError {
+    pub kind: YaccErrorKind,
+    pub span: Span
+}
+
+impl YaccError {
+    /// Render this error as a `line:col: message`, caret-underlined diagnostic against `src`
+    /// (the same string that was passed to [`parse_yacc`](fn.parse_yacc.html)).
+    pub fn pp(&self, src: &str) -> String {
+        let cache = NewlineCache::new(src);
+        render(src, &cache, self.span, self)
+    }
+}
+
+impl fmt::Display for YaccError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self.kind {
+            YaccErrorKind::IncompleteRule => "Incomplete rule",
+            YaccErrorKind::MissingColon => "Missing colon",
+            YaccErrorKind::PrematureEnd => "File ends prematurely",
+            YaccErrorKind::ProgramsNotSupported => "Programs not currently supported",
+            YaccErrorKind::UnknownDeclaration => "Unknown declaration"
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Error for YaccError {}
+
+struct YaccParser<'a> {
+    src: &'a str,
+    i: usize
+}
+
+impl<'a> YaccParser<'a> {
+    fn new(src: &'a str) -> Self {
+        YaccParser { src, i: 0 }
+    }
+
+    fn err(&self, kind: YaccErrorKind) -> YaccError {
+        YaccError {
+            kind,
+            span: Span::new(self.i, (self.i + 1).min(self.src.len().max(self.i)))
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.i..]
+    }
+
+    fn is_eof(&self) -> bool {
+        self.i >= self.src.len()
+    }
+
+    fn skip_ws(&mut self) {
+        while !self.is_eof() {
+            let c = self.rest().chars().next().unwrap();
+            if c.is_whitespace() {
+                self.i += c.len_utf8();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn starts_with(&self, pat: &str) -> bool {
+        self.rest().starts_with(pat)
+    }
+
+    /// Consume a contiguous run of identifier characters, returning it (or `None` if the cursor
+    /// isn't on one).
+    fn parse_ident(&mut self) -> Option<&'a str> {
+        let start = self.i;
+        while !self.is_eof() {
+            let c = self.rest().chars().next().unwrap();
+            if c.is_alphanumeric() || c == '_' {
+                self.i += c.len_utf8();
+            } else {
+                break;
+            }
+        }
+        if self.i == start {
+            None
+        } else {
+            Some(&self.src[start..self.i])
+        }
+    }
+
+    /// Parse the `%start`/`%token` declarations before the first `%%`.
+    fn parse_header(&mut self, grm: &mut GrammarAST) -> Result<(), YaccError> {
+        loop {
+            self.skip_ws();
+            if self.is_eof() {
+                return Err(self.err(YaccErrorKind::PrematureEnd));
+            }
+            if self.starts_with("%%") {
+                self.i += 2;
+                return Ok(());
+            }
+            if !self.starts_with("%") {
+                return Err(self.err(YaccErrorKind::UnknownDeclaration));
+            }
+            self.i += 1;
+            let kw = self.parse_ident().ok_or_else(|| self.err(YaccErrorKind::UnknownDeclaration))?;
+            match kw {
+                "start" => {
+                    self.skip_ws();
+                    let name = self
+                        .parse_ident()
+                        .ok_or_else(|| self.err(YaccErrorKind::PrematureEnd))?;
+                    grm.start = Some(name.to_string());
+                }
+                "token" => loop {
+                    self.skip_ws();
+                    if self.is_eof() || self.starts_with("%") {
+                        break;
+                    }
+                    match self.parse_ident() {
+                        Some(name) => {
+                            grm.tokens.insert(name.to_string());
+                        }
+                        None => break
+                    }
+                },
+                _ => return Err(self.err(YaccErrorKind::UnknownDeclaration))
+            }
+        }
+    }
+
+    /// Parse a single quoted (`'...'` or `"..."`) terminal.
+    fn parse_quoted_terminal(&mut self) -> Result<Symbol, YaccError> {
+        let quote = self.rest().chars().next().unwrap();
+        self.i += quote.len_utf8();
+        let start = self.i;
+        while !self.is_eof() && self.rest().chars().next() != Some(quote) {
+            let c = self.rest().chars().next().unwrap();
+            self.i += c.len_utf8();
+        }
+        if self.is_eof() {
+            return Err(self.err(YaccErrorKind::IncompleteRule));
+        }
+        let name = &self.src[start..self.i];
+        self.i += quote.len_utf8();
+        Ok(Symbol::Terminal(name.to_string()))
+    }
+
+    /// Parse the rules section (everything after the first `%%`).
+    fn parse_rules(&mut self, grm: &mut GrammarAST) -> Result<(), YaccError> {
+        loop {
+            self.skip_ws();
+            if self.is_eof() {
+                return Ok(());
+            }
+            if self.starts_with("%%") {
+                self.i += 2;
+                if self.rest().trim().is_empty() {
+                    return Ok(());
+                }
+                return Err(self.err(YaccErrorKind::ProgramsNotSupported));
+            }
+
+            let name_start = self.i;
+            let name = self
+                .parse_ident()
+                .ok_or_else(|| self.err(YaccErrorKind::UnknownDeclaration))?
+                .to_string();
+            let _ = name_start;
+
+            self.skip_ws();
+            if self.is_eof() {
+                return Err(self.err(YaccErrorKind::IncompleteRule));
+            }
+            if !self.starts_with(":") {
+                return Err(self.err(YaccErrorKind::MissingColon));
+            }
+            self.i += 1;
+
+            let rule = grm
+                .rules
+                .entry(name.clone())
+                .or_insert_with(|| Rule::new(name.clone()));
+            let mut alt = Vec::new();
+            loop {
+                self.skip_ws();
+                if self.is_eof() {
+                    return Err(self.err(YaccErrorKind::IncompleteRule));
+                }
+                if self.starts_with(";") {
+                    self.i += 1;
+                    rule.alternatives.push(alt);
+                    break;
+                }
+                if self.starts_with("|") {
+                    self.i += 1;
+                    rule.alternatives.push(alt);
+                    alt = Vec::new();
+                    continue;
+                }
+                let c = self.rest().chars().next().unwrap();
+                if c == '\'' || c == '"' {
+                    let sym = self.parse_quoted_terminal()?;
+                    if let Symbol::Terminal(ref n) = sym {
+                        grm.tokens.insert(n.clone());
+                    }
+                    alt.push(sym);
+                } else {
+                    match self.parse_ident() {
+                        Some(n) => alt.push(Symbol::Nonterminal(n.to_string())),
+                        None => return Err(self.err(YaccErrorKind::IncompleteRule))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parse the textual contents of a `.y` file into a [`GrammarAST`](../grammar_ast/struct.GrammarAST.html).
+/// Note that this only parses and builds the AST: callers should call
+/// [`GrammarAST::validate`](../grammar_ast/struct.GrammarAST.html#method.validate) themselves
+/// before relying on its consistency.
+pub fn parse_yacc(s: &str) -> Result<GrammarAST, YaccError> {
+    let mut grm = GrammarAST::new();
+    let mut p = YaccParser::new(s);
+    p.parse_header(&mut grm)?;
+    p.parse_rules(&mut grm)?;
+    Ok(grm)
+}
@@ -0,0 +1,41 @@
+extern crate lrpar;
+
+use lrpar::cactus::Cactus;
+
+#[test]
+fn test_push_pop_peek() {
+    let c = Cactus::new();
+    assert!(c.is_empty());
+    assert_eq!(c.len(), 0);
+    assert_eq!(c.peek(), None);
+
+    let c1 = c.push(1);
+    assert_eq!(c1.peek(), Some(&1));
+    assert_eq!(c1.len(), 1);
+
+    let c2 = c1.push(2);
+    assert_eq!(c2.len(), 2);
+    assert_eq!(c2.peek(), Some(&2));
+
+    let (c3, popped) = c2.pop();
+    assert_eq!(popped, Some(&2));
+    assert_eq!(c3.len(), 1);
+    assert_eq!(c3.peek(), Some(&1));
+
+    // Popping c2 must not disturb c1, which shares c2's tail.
+    assert_eq!(c1.peek(), Some(&1));
+}
+
+#[test]
+fn test_diverging_stacks_share_a_prefix() {
+    let base = Cactus::new().push(1).push(2);
+    let left = base.push(3);
+    let right = base.push(4);
+    assert_eq!(left.len(), 3);
+    assert_eq!(right.len(), 3);
+
+    let (left_popped, _) = left.pop();
+    assert_eq!(left_popped.peek(), Some(&2));
+    let (right_popped, _) = right.pop();
+    assert_eq!(right_popped.peek(), Some(&2));
+}
@@ -0,0 +1,71 @@
+extern crate lrpar;
+
+use std::time::Duration;
+
+use lrpar::cactus::Cactus;
+use lrpar::cpctplus::{recover, ParseRepair, RecoveryTable, SHIFTS_TO_RECOVER};
+
+/// A tiny 4-state table used only to exercise `recover`'s search. Token ids: A=0, B=1, C=2.
+///
+/// State 0 can only shift A (to state 1); state 1 can't shift B directly, but reduces on it
+/// (popping 1 frame) to state 2, from which B can be shifted (to state 3); state 3 shifts C back
+/// to itself, so a string of Cs can be shifted indefinitely once there. This means the cheapest
+/// way to recover from a stream of Cs while sat in state 0 is to insert A, then insert B (via
+/// the reduce-then-shift path), then shift the real Cs.
+struct TestTable;
+
+impl RecoveryTable<u8> for TestTable {
+    type StateIdx = u8;
+
+    fn shift(&self, state: u8, tok: u8) -> Option<u8> {
+        match (state, tok) {
+            (0, 0) => Some(1),
+            (2, 1) => Some(3),
+            (3, 2) => Some(3),
+            _ => None
+        }
+    }
+
+    fn reduce(&self, state: u8, tok: u8) -> Option<(usize, u8)> {
+        match (state, tok) {
+            (1, 1) => Some((1, 2)),
+            _ => None
+        }
+    }
+
+    fn insertable(&self, state: u8) -> Vec<u8> {
+        match state {
+            0 => vec![0],
+            1 => vec![1],
+            2 => vec![1],
+            3 => vec![2],
+            _ => vec![]
+        }
+    }
+}
+
+#[test]
+fn test_insert_via_reduce_then_shift() {
+    let table = TestTable;
+    let pstack = Cactus::new().push(0u8);
+    let input = vec![2u8, 2, 2];
+    let repairs = recover(&table, pstack, &input, 0, Duration::from_millis(500));
+
+    assert!(!repairs.is_empty());
+    let best = &repairs[0];
+    assert_eq!(best[0], ParseRepair::Insert(0));
+    assert_eq!(best[1], ParseRepair::Insert(1));
+    let shifts = best.iter().filter(|r| **r == ParseRepair::Shift).count();
+    assert_eq!(shifts as u32, SHIFTS_TO_RECOVER);
+}
+
+#[test]
+fn test_no_repair_possible_falls_back_to_panic_mode() {
+    let table = TestTable;
+    // State 3 can only ever shift C (token 2): asking it to recover against a token it can
+    // neither shift, reduce, nor insert its way out of should fall back to panic-mode deletion.
+    let pstack = Cactus::new().push(3u8);
+    let input = vec![9u8]; // not a token this table knows about
+    let repairs = recover(&table, pstack, &input, 0, Duration::from_millis(50));
+    assert_eq!(repairs, vec![vec![ParseRepair::Delete]]);
+}
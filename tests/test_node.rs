@@ -0,0 +1,57 @@
+extern crate lrpar;
+
+use lrpar::grammar::Grammar;
+use lrpar::{ActionKind, Lexeme, Node, RTParserBuilder};
+
+#[test]
+fn test_pp_renders_indented_tree() {
+    let grm = Grammar {
+        start: None,
+        rule_names: vec!["E".to_string(), "T".to_string()]
+    };
+    let input = "1+2";
+    let tree = Node::Nonterm {
+        ridx: 0,
+        nodes: vec![
+            Node::Nonterm {
+                ridx: 1,
+                nodes: vec![Node::Term { lexeme: Lexeme::new(0u8, 0, 1) }]
+            },
+            Node::Term { lexeme: Lexeme::new(1u8, 1, 1) },
+            Node::Nonterm {
+                ridx: 1,
+                nodes: vec![Node::Term { lexeme: Lexeme::new(0u8, 2, 1) }]
+            }
+        ]
+    };
+    let pp = tree.pp(&grm, input);
+    assert_eq!(pp, "E\n  T\n    1\n  +\n  T\n    2\n");
+}
+
+#[test]
+fn test_pp_unknown_rule_name() {
+    let grm = Grammar { start: None, rule_names: vec![] };
+    let tree: Node<u8> = Node::Nonterm { ridx: 5, nodes: vec![] };
+    assert_eq!(tree.pp(&grm, ""), "<unknown rule>\n");
+}
+
+#[test]
+fn test_generic_parse_tree_builders() {
+    let builder = RTParserBuilder::new().actioner(ActionKind::GenericParseTree);
+    let leaf = builder.term_node(Lexeme::new(0u8, 0, 3));
+    let tree = builder.nonterm_node(0, vec![leaf]);
+    match tree {
+        Node::Nonterm { ridx, ref nodes } => {
+            assert_eq!(ridx, 0);
+            assert_eq!(nodes.len(), 1);
+        }
+        _ => panic!("expected a Nonterm node")
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_generic_parse_tree_builders_panic_in_user_action_mode() {
+    let builder = RTParserBuilder::new().actioner(ActionKind::UserAction);
+    let _ = builder.term_node(Lexeme::new(0u8, 0, 1));
+}
@@ -220,3 +220,13 @@ fn test_unknown_declaration() {
         Err(e) => panic!("Incorrect error returned {}", e)
     }
 }
+
+#[test]
+fn test_pp_points_at_the_offending_line() {
+    let src = "%%\nA x;".to_string();
+    let err = parse_yacc(&src).unwrap_err();
+    assert_eq!(err.kind, YaccErrorKind::MissingColon);
+    let rendered = err.pp(&src);
+    assert!(rendered.starts_with("2:"));
+    assert!(rendered.contains("A x;"));
+}